@@ -0,0 +1,119 @@
+/*
+ *   This file is part of NCC Group Scrying https://github.com/nccgroup/scrying
+ *   Copyright 2020 David Young <david(dot)young(at)nccgroup(dot)com>
+ *   Released as open source by NCC Group Plc - https://www.nccgroup.com
+ *
+ *   Scrying is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Scrying is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Scrying.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::path::PathBuf;
+use std::str::FromStr;
+use structopt::StructOpt;
+
+/// Command line options, parsed by structopt from `std::env::args`.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "scrying")]
+pub struct Opts {
+    /// Directory to save captures into
+    #[structopt(short, long, default_value = "output")]
+    pub output_dir: PathBuf,
+
+    /// Capture the entire scrollable page rather than just the visible
+    /// viewport (web targets, webkit backend only)
+    #[structopt(long)]
+    pub full_page: bool,
+
+    /// Output format for web captures (webkit backend only; the
+    /// webdriver backend always saves PNG)
+    #[structopt(
+        long,
+        default_value = "png",
+        possible_values = &["png", "pdf"]
+    )]
+    pub web_format: WebFormat,
+
+    /// Allow file:// targets to be loaded. Off by default since loading
+    /// a local file path gives a target read access to the filesystem
+    /// Scrying runs on.
+    #[structopt(long)]
+    pub allow_file: bool,
+
+    /// Path to a JS file to inject into every frame of every web target
+    /// before capture (webkit backend only). May be given multiple times.
+    #[structopt(long)]
+    pub inject_js: Vec<PathBuf>,
+
+    /// Path to a CSS file to inject into every frame of every web
+    /// target before capture (webkit backend only). May be given
+    /// multiple times.
+    #[structopt(long)]
+    pub inject_css: Vec<PathBuf>,
+
+    /// Which engine to capture web targets with
+    #[structopt(
+        long,
+        default_value = "webkit",
+        possible_values = &["webkit", "webdriver"]
+    )]
+    pub web_backend: WebBackend,
+
+    /// URL of a running WebDriver endpoint (chromedriver/geckodriver),
+    /// used when `--web-backend webdriver` is selected
+    #[structopt(long, default_value = "http://localhost:4444")]
+    pub webdriver_url: String,
+
+    /// Seconds to wait for a web target to finish loading and be
+    /// captured before giving up on it
+    #[structopt(long, default_value = "10")]
+    pub web_timeout: u64,
+}
+
+/// Output format for a web capture, selected with `--web-format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebFormat {
+    Png,
+    Pdf,
+}
+
+impl FromStr for WebFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "png" => Ok(WebFormat::Png),
+            "pdf" => Ok(WebFormat::Pdf),
+            other => Err(format!("Unknown web format `{}`", other)),
+        }
+    }
+}
+
+/// Which engine to drive for web captures, selected with
+/// `--web-backend`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebBackend {
+    Webkit,
+    Webdriver,
+}
+
+impl FromStr for WebBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "webkit" => Ok(WebBackend::Webkit),
+            "webdriver" => Ok(WebBackend::Webdriver),
+            other => Err(format!("Unknown web backend `{}`", other)),
+        }
+    }
+}
@@ -0,0 +1,62 @@
+/*
+ *   This file is part of NCC Group Scrying https://github.com/nccgroup/scrying
+ *   Copyright 2020 David Young <david(dot)young(at)nccgroup(dot)com>
+ *   Released as open source by NCC Group Plc - https://www.nccgroup.com
+ *
+ *   Scrying is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Scrying is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Scrying.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::fmt;
+use std::path::PathBuf;
+use url::Url;
+
+/// A single capture target, as produced by parsing one line of an input
+/// file or one positional argument. `Url`/`File` are consumed by the web
+/// worker; the others are handled by their own, separately-driven
+/// workers.
+#[derive(Debug, Clone)]
+pub enum Target {
+    Url(Url),
+    File(PathBuf),
+    Rdp(String),
+    Vnc(String),
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Url(u) => write!(f, "{}", u),
+            Target::File(path) => write!(f, "{}", path.display()),
+            Target::Rdp(addr) => write!(f, "rdp://{}", addr),
+            Target::Vnc(addr) => write!(f, "vnc://{}", addr),
+        }
+    }
+}
+
+// Turn one line of raw input into a Target. Anything that parses as a
+// URL is taken at face value, except that an explicit `file://` URL
+// becomes a `Target::File` of its path; anything else is assumed to be a filesystem path and turned into a
+// `Target::File` rather than being rejected, so local paths dropped
+// into a targets file work without needing a `file://` prefix. Whether
+// a `Target::File` is actually loaded is up to the caller, gated behind
+// `--allow-file`.
+pub fn parse_target(raw: &str) -> Target {
+    match Url::parse(raw) {
+        Ok(url) if url.scheme() == "file" => {
+            Target::File(PathBuf::from(url.path()))
+        }
+        Ok(url) => Target::Url(url),
+        Err(_) => Target::File(PathBuf::from(raw)),
+    }
+}
@@ -19,23 +19,36 @@
 
 use super::{save, HEIGHT, WIDTH};
 use crate::{
-    argparse::Opts, parsing::Target, reporting::ReportMessage, InputLists,
+    argparse::{Opts, WebFormat},
+    parsing::Target,
+    reporting::ReportMessage,
+    InputLists,
 };
+use async_channel::{Receiver, Sender};
+use async_io::Timer;
+use futures_lite::{future::block_on, FutureExt};
 use gdk::prelude::WindowExtManual;
 use gio::prelude::*;
 use gtk::{
-    Application, ApplicationWindow, ContainerExt, GtkWindowExt, WidgetExt,
-    WindowPosition,
+    Application, ApplicationWindow, ContainerExt, GtkWindowExt, PrintSettings,
+    WidgetExt, WindowPosition,
 };
 #[allow(unused)]
 use log::{debug, error, info, trace, warn};
 use std::sync::{
-    atomic::{AtomicBool, Ordering},
+    atomic::{AtomicBool, AtomicU64, Ordering},
     mpsc, Arc,
 };
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
 use std::{thread, time::Duration};
 use webkit2gtk::{
-    UserContentManager, WebContext, WebView, WebViewExt, WebViewExtManual,
+    PrintOperation, SnapshotOptions, SnapshotRegion,
+    UserContentInjectedFrames, UserContentManager, UserContentManagerExt,
+    UserScript, UserScriptInjectionTime, UserStyleLevel, UserStyleSheet,
+    WebContext, WebView, WebViewExt, WebViewExtManual,
 };
 
 enum GuiMessage {
@@ -44,6 +57,262 @@ enum GuiMessage {
     PageReady,
 }
 
+// What a capture attempt produced. PDF exports are written straight to
+// disk by webkit (see `print_to_pdf`), so unlike the PNG variants they
+// carry the path that was written rather than bytes to write.
+enum CaptureResult {
+    Png(Vec<u8>),
+    Pdf(std::path::PathBuf),
+}
+
+// A capture result tagged with the generation of the target it belongs
+// to (see `send_capture_result`/`wait_for_capture`), since the bounded
+// img channel can otherwise hand the supervisor a result left over from
+// a target it already abandoned.
+type CaptureMsg = (u64, Result<CaptureResult, String>);
+
+// Request an asynchronous full-document snapshot from webkit2gtk and
+// forward the resulting PNG bytes down `img_tx` once the callback fires.
+// This replaces the fixed-viewport pixbuf grab with a capture sized to
+// the entire rendered page.
+fn request_full_page_snapshot(
+    webview: &WebView,
+    generation: u64,
+    img_tx: Sender<CaptureMsg>,
+) {
+    webview.get_snapshot(
+        SnapshotRegion::FullDocument,
+        SnapshotOptions::NONE,
+        None::<&gio::Cancellable>,
+        move |result| match result {
+            Ok(surface) => {
+                let mut buf = Vec::new();
+                match surface.write_to_png(&mut buf) {
+                    Ok(()) => {
+                        trace!("Got full-page snapshot length {}", buf.len());
+                        send_capture_result(
+                            &img_tx,
+                            generation,
+                            Ok(CaptureResult::Png(buf)),
+                        );
+                    }
+                    Err(e) => {
+                        send_capture_result(
+                            &img_tx,
+                            generation,
+                            Err(format!(
+                                "Failed to encode snapshot as PNG: {}",
+                                e
+                            )),
+                        );
+                    }
+                }
+            }
+            Err(e) => {
+                send_capture_result(
+                    &img_tx,
+                    generation,
+                    Err(format!("Failed to capture snapshot: {}", e)),
+                );
+            }
+        },
+    );
+}
+
+// Send a capture result down the bounded(1) img channel, tagged with
+// the generation of the target it belongs to. A result the supervisor
+// already gave up on (timeout/interrupt) can still arrive after it has
+// moved on to the next target; if it's still sitting in the channel
+// when this one turns up, evict it first rather than let a plain
+// `try_send` on a full channel silently drop *this*, current, result
+// instead. `wait_for_capture` discards anything whose tag doesn't
+// match the generation it's waiting on, so a stale result that slips
+// through regardless can never be attributed to the wrong target.
+fn send_capture_result(
+    tx: &Sender<CaptureMsg>,
+    generation: u64,
+    msg: Result<CaptureResult, String>,
+) {
+    if tx.force_send((generation, msg)).is_err() {
+        warn!("Failed to send capture result: receiver has gone away");
+    }
+}
+
+// Turn a URI into a filesystem-safe stem for the exported PDF, replacing
+// anything that isn't alphanumeric with an underscore.
+fn uri_to_filename_stem(uri: &str) -> String {
+    uri.chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+// Drive webkit2gtk's print pipeline with the Export action so the fully
+// rendered, paginated DOM is written straight to a PDF on disk, instead
+// of going through the pixbuf/PNG path. Reports completion (or failure)
+// down `img_tx` so the supervisor thread's blocking `recv` unblocks; the
+// PDF bytes themselves are never sent over the channel since webkit has
+// already written them to `output_path` — the supervisor reports that
+// path directly rather than routing it through `save`'s PNG write.
+fn print_to_pdf(
+    webview: &WebView,
+    output_path: &std::path::Path,
+    generation: u64,
+    img_tx: Sender<CaptureMsg>,
+) {
+    let print_settings = PrintSettings::new();
+    print_settings
+        .set("output-uri", &format!("file://{}", output_path.display()));
+    print_settings.set("output-file-format", "pdf");
+
+    let operation = PrintOperation::new(webview);
+    operation.set_print_settings(&print_settings);
+
+    // `print` only kicks the (asynchronous) export off and returns
+    // nothing useful; the actual outcome arrives via the `failed`
+    // signal (export error) or `finished` signal (export completed,
+    // successfully or not). Neither signal keeps `operation` alive on
+    // our behalf, so stash a strong ref in each closure -- otherwise
+    // the local binding above drops it the moment `print_to_pdf`
+    // returns and webkit cancels the export before either fires.
+    let op_keepalive = Rc::new(RefCell::new(Some(operation.clone())));
+
+    // `finished` fires whether the export succeeded or not, so it alone
+    // can't tell success from failure; it's only safe to report success
+    // from it once we know `failed` didn't already fire first.
+    let export_failed = Rc::new(Cell::new(false));
+
+    let failed_path = output_path.to_path_buf();
+    let failed_img_tx = img_tx.clone();
+    let op_keepalive_failed = op_keepalive.clone();
+    let export_failed_clone = export_failed.clone();
+    operation.connect_failed(move |_op, error| {
+        warn!("PDF export of {} failed: {}", failed_path.display(), error);
+        export_failed_clone.set(true);
+        send_capture_result(
+            &failed_img_tx,
+            generation,
+            Err(format!("Failed to export PDF: {}", error)),
+        );
+        op_keepalive_failed.borrow_mut().take();
+    });
+
+    let done_path = output_path.to_path_buf();
+    operation.connect_finished(move |_op| {
+        if export_failed.get() {
+            trace!(
+                "Export of {} already reported as failed; ignoring \
+                 finished signal",
+                done_path.display()
+            );
+            op_keepalive.borrow_mut().take();
+            return;
+        }
+        trace!("Exported PDF to {}", done_path.display());
+        send_capture_result(
+            &img_tx,
+            generation,
+            Ok(CaptureResult::Pdf(done_path.clone())),
+        );
+        op_keepalive.borrow_mut().take();
+    });
+
+    operation.print();
+}
+
+// Load the --inject-js/--inject-css files from Opts and register them
+// on the UserContentManager so they run on every frame of every
+// navigated target for the lifetime of the WebView, e.g. to auto-dismiss
+// cookie banners or force-expand lazy content before capture.
+fn register_injections(manager: &UserContentManager, opts: &Opts) {
+    for path in &opts.inject_js {
+        match std::fs::read_to_string(path) {
+            Ok(source) => {
+                let script = UserScript::new(
+                    &source,
+                    UserContentInjectedFrames::AllFrames,
+                    UserScriptInjectionTime::End,
+                    &[],
+                    &[],
+                );
+                manager.add_script(&script);
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to read --inject-js file `{}`: {}",
+                    path.display(),
+                    e
+                );
+            }
+        }
+    }
+
+    for path in &opts.inject_css {
+        match std::fs::read_to_string(path) {
+            Ok(source) => {
+                let stylesheet = UserStyleSheet::new(
+                    &source,
+                    UserContentInjectedFrames::AllFrames,
+                    UserStyleLevel::User,
+                    &[],
+                    &[],
+                );
+                manager.add_style_sheet(&stylesheet);
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to read --inject-css file `{}`: {}",
+                    path.display(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+// Wait for the GUI side to deliver a capture result tagged with
+// `generation`, abandoning the wait (rather than blocking forever) if
+// `timeout` elapses or if `caught_ctrl_c` is set while we're waiting.
+// The outer `Result` carries those abandonment reasons; the inner one
+// is the capture outcome that `img_tx` normally carries. A result left
+// over from a target we already gave up on can still arrive tagged
+// with an earlier generation -- discard it and keep waiting rather than
+// handing it to the caller as if it belonged to `generation`.
+fn wait_for_capture(
+    img_rx: &Receiver<CaptureMsg>,
+    generation: u64,
+    timeout: Duration,
+    caught_ctrl_c: &AtomicBool,
+) -> Result<Result<CaptureResult, String>, String> {
+    let recv = async {
+        loop {
+            let (tag, result) = img_rx
+                .recv()
+                .await
+                .map_err(|e| format!("Channel disconnected: {}", e))?;
+            if tag == generation {
+                return Ok(result);
+            }
+            trace!(
+                "Discarding capture result from generation {} while \
+                 waiting on generation {}",
+                tag,
+                generation
+            );
+        }
+    };
+    let timed_out = async {
+        Timer::after(timeout).await;
+        Err("Timed out waiting for page to finish loading".to_string())
+    };
+    let interrupted = async {
+        while !caught_ctrl_c.load(Ordering::SeqCst) {
+            Timer::after(Duration::from_millis(100)).await;
+        }
+        Err("Interrupted by Ctrl+C".to_string())
+    };
+    block_on(recv.or(timed_out).or(interrupted))
+}
+
 pub fn web_worker(
     targets: Arc<InputLists>,
     opts: Arc<Opts>,
@@ -60,6 +329,12 @@ pub fn web_worker(
     // the target list has been exhausted
     let targets_exhausted = Arc::new(AtomicBool::new(false));
     let targets_exhausted_clone = targets_exhausted.clone();
+    // Bumped by the supervisor thread before navigating to each target
+    // and read back by the GUI side when it produces a result, so a
+    // result can be tagged with the target it actually belongs to (see
+    // `send_capture_result`/`wait_for_capture`).
+    let generation = Arc::new(AtomicU64::new(0));
+    let generation_clone = generation.clone();
     application.connect_activate(move |app| {
         let window = ApplicationWindow::new(app);
         window.set_default_size(WIDTH, HEIGHT);
@@ -69,37 +344,35 @@ pub fn web_worker(
 
         // Create a webview
         let manager = UserContentManager::new();
+        register_injections(&manager, &opts);
         let context = WebContext::new();
         let webview = WebView::new_with_context_and_user_content_manager(
             &context, &manager,
         );
 
-        // Make a channel for sending captured images back to the
-        // supervisor thread
-        let (img_tx, img_rx) = mpsc::channel::<Result<Vec<u8>, String>>();
+        // Channel for sending captured images back to the supervisor
+        // thread. async_channel lets the supervisor `recv` (with a
+        // timeout, see `wait_for_capture`) and this side `try_send`
+        // without either end needing a dedicated relay thread. Bounded
+        // to 1: only one target is ever in flight, and each message is
+        // tagged with a generation (see `CaptureMsg`) so a result left
+        // over from an abandoned target can't be mistaken for the
+        // current one.
+        let (img_tx, img_rx) = async_channel::bounded::<CaptureMsg>(1);
 
         let targets_exhausted_clone = targets_exhausted_clone.clone();
         webview.connect_ready_to_show(move |_wv| {
             info!("Ready to show!");
-            //img_tx.send(Ok(Vec::new())).unwrap();
         });
 
-        // Create a communication channel
+        // Communication channel to the GTK main loop
         let main_context = glib::MainContext::default();
-        let (sender, receiver) =
-            glib::MainContext::channel::<GuiMessage>(glib::Priority::default());
-
-        let gui_sender = sender.clone();
-        let (delayed_gui_sender, delayed_gui_receiver) =
-            mpsc::channel::<GuiMessage>();
-
-        thread::spawn(move || {
-            while let Ok(msg) = delayed_gui_receiver.recv() {
-                thread::sleep(Duration::from_millis(1000));
-                gui_sender.send(msg).unwrap();
-            }
-        });
+        let (sender, receiver) = async_channel::unbounded::<GuiMessage>();
 
+        let opts_clone = opts.clone();
+        let img_tx_clone = img_tx.clone();
+        let sender_for_delay = sender.clone();
+        let generation_for_load = generation_clone.clone();
         webview.connect_load_changed(move |wv, evt| {
             use webkit2gtk::LoadEvent::*;
             trace!(
@@ -114,8 +387,77 @@ pub fn web_worker(
             }
             match evt {
                 Finished => {
-                    // grab screenshot
-                    delayed_gui_sender.send(GuiMessage::PageReady).unwrap();
+                    let generation =
+                        generation_for_load.load(Ordering::SeqCst);
+                    if let WebFormat::Pdf = opts_clone.web_format {
+                        let stem = wv
+                            .get_uri()
+                            .map(|u| uri_to_filename_stem(u.as_str()))
+                            .unwrap_or_else(|| "capture".to_string());
+                        // `output_dir` is frequently relative (e.g. the
+                        // default `output`); canonicalize it first so the
+                        // `file://` URI built in `print_to_pdf` is always
+                        // absolute (`file:///...`) rather than being
+                        // parsed with the first path segment as a host.
+                        if let Err(e) =
+                            std::fs::create_dir_all(&opts_clone.output_dir)
+                        {
+                            warn!(
+                                "Failed to create output directory `{}`: {}",
+                                opts_clone.output_dir.display(),
+                                e
+                            );
+                            return;
+                        }
+                        let output_dir =
+                            match opts_clone.output_dir.canonicalize() {
+                                Ok(dir) => dir,
+                                Err(e) => {
+                                    warn!(
+                                        "Failed to resolve output directory \
+                                     `{}`: {}",
+                                        opts_clone.output_dir.display(),
+                                        e
+                                    );
+                                    return;
+                                }
+                            };
+                        let output_path =
+                            output_dir.join(format!("{}.pdf", stem));
+                        print_to_pdf(
+                            wv,
+                            &output_path,
+                            generation,
+                            img_tx_clone.clone(),
+                        );
+                    } else if opts_clone.full_page {
+                        // Full-document capture completes asynchronously;
+                        // the snapshot callback sends the PNG bytes itself.
+                        request_full_page_snapshot(
+                            wv,
+                            generation,
+                            img_tx_clone.clone(),
+                        );
+                    } else {
+                        // Give the page a moment to settle before
+                        // grabbing the viewport, without blocking a
+                        // dedicated relay thread: schedule the delayed
+                        // send as a task on this same main context.
+                        let sender_clone = sender_for_delay.clone();
+                        glib::MainContext::default().spawn_local(async move {
+                            Timer::after(Duration::from_millis(1000)).await;
+                            if sender_clone
+                                .send(GuiMessage::PageReady)
+                                .await
+                                .is_err()
+                            {
+                                warn!(
+                                    "GUI channel closed before PageReady \
+                                         could be sent"
+                                );
+                            }
+                        });
+                    }
                 }
                 _ => {}
             }
@@ -124,49 +466,70 @@ pub fn web_worker(
         window.add(&webview);
         window.show_all();
 
-        receiver.attach(Some(&main_context), move |msg| match msg {
-            GuiMessage::Navigate(u) => {
-                trace!("Navigating to target: {}", u);
-                webview.load_uri(&u);
-                glib::source::Continue(true)
-            }
-            GuiMessage::Exit => {
-                info!("Exit signal received, closing window");
-                window.close();
-                glib::source::Continue(false)
-            }
-            GuiMessage::PageReady => {
-                if let Some(win) = webview.get_window() {
-                    match win.get_pixbuf(0, 0, WIDTH, HEIGHT) {
-                        Some(pix) => match pix.save_to_bufferv("png", &[]) {
-                            Ok(buf) => {
-                                trace!("Got pixbuf length {}", buf.len());
-                                img_tx.send(Ok(buf)).unwrap();
-                            }
-                            Err(e) => {
-                                img_tx
-                                    .send(Err(format!(
-                                        "Failed to process pixbuf: {}",
-                                        e
-                                    )))
-                                    .unwrap();
+        let generation_for_gui = generation_clone.clone();
+        main_context.spawn_local(async move {
+            while let Ok(msg) = receiver.recv().await {
+                match msg {
+                    GuiMessage::Navigate(u) => {
+                        trace!("Navigating to target: {}", u);
+                        webview.load_uri(&u);
+                    }
+                    GuiMessage::Exit => {
+                        info!("Exit signal received, closing window");
+                        window.close();
+                        break;
+                    }
+                    GuiMessage::PageReady => {
+                        let generation =
+                            generation_for_gui.load(Ordering::SeqCst);
+                        if let Some(win) = webview.get_window() {
+                            match win.get_pixbuf(0, 0, WIDTH, HEIGHT) {
+                                Some(pix) => {
+                                    match pix.save_to_bufferv("png", &[]) {
+                                        Ok(buf) => {
+                                            trace!(
+                                                "Got pixbuf length {}",
+                                                buf.len()
+                                            );
+                                            send_capture_result(
+                                                &img_tx,
+                                                generation,
+                                                Ok(CaptureResult::Png(buf)),
+                                            );
+                                        }
+                                        Err(e) => {
+                                            send_capture_result(
+                                                &img_tx,
+                                                generation,
+                                                Err(format!(
+                                                    "Failed to process \
+                                                     pixbuf: {}",
+                                                    e
+                                                )),
+                                            );
+                                        }
+                                    }
+                                }
+                                None => {
+                                    send_capture_result(
+                                        &img_tx,
+                                        generation,
+                                        Err("Failed to retrieve pixbuf"
+                                            .to_string()),
+                                    );
+                                }
                             }
-                        },
-                        None => {
-                            img_tx
-                                .send(Err(
-                                    "Failed to retrieve pixbuf".to_string()
-                                ))
-                                .unwrap();
+                        } else {
+                            send_capture_result(
+                                &img_tx,
+                                generation,
+                                Err("Unable to find window".to_string()),
+                            );
                         }
                     }
-                } else {
-                    img_tx
-                        .send(Err("Unable to find window".to_string()))
-                        .unwrap();
                 }
-                glib::source::Continue(true)
             }
+            trace!("GUI message channel closed, ending GTK task");
         });
 
         let targets_clone = targets.clone();
@@ -174,6 +537,7 @@ pub fn web_worker(
         let opts_clone = opts.clone();
         let targets_exhausted_clone = targets_exhausted.clone();
         let caught_ctrl_c_clone = caught_ctrl_c.clone();
+        let generation_for_supervisor = generation_clone;
         thread::spawn(move || {
             for target in &targets_clone.web_targets {
                 // If ctrl+c has been pressed then don't send any more targets
@@ -181,18 +545,49 @@ pub fn web_worker(
                     break;
                 }
 
-                if let Target::Url(u) = target {
-                    sender
-                        .send(GuiMessage::Navigate(u.as_str().to_string()))
-                        .unwrap();
-                } else {
-                    warn!("Target `{}` is not a URL!", target);
-                    continue;
+                let uri = match target {
+                    Target::Url(u) => u.as_str().to_string(),
+                    Target::File(path) => {
+                        if !opts_clone.allow_file {
+                            warn!(
+                                "Refusing to load local file target `{}` \
+                                 without --allow-file",
+                                target
+                            );
+                            continue;
+                        }
+                        // No sanitization of the path is performed here;
+                        // --allow-file is an explicit opt-in to the
+                        // local-file-read and SSRF-style risks of
+                        // loading file:// content.
+                        format!("file://{}", path.display())
+                    }
+                    _ => {
+                        warn!("Target `{}` is not a URL!", target);
+                        continue;
+                    }
+                };
+                // Bump the generation before navigating so any result
+                // the GUI side produces for this target -- and only
+                // this target -- is tagged with it.
+                let target_generation = generation_for_supervisor
+                    .fetch_add(1, Ordering::SeqCst)
+                    + 1;
+                if sender.send_blocking(GuiMessage::Navigate(uri)).is_err() {
+                    warn!("GUI channel closed, aborting capture run");
+                    break;
                 }
 
-                // Wait for a response
-                match img_rx.recv() {
-                    Ok(Ok(img)) => {
+                // Wait for a response, abandoning the target if it takes
+                // longer than --web-timeout or if ctrl+c is pressed
+                // while we're waiting on it.
+                match wait_for_capture(
+                    &img_rx,
+                    target_generation,
+                    Duration::from_secs(opts_clone.web_timeout),
+                    &caught_ctrl_c_clone,
+                ) {
+                    Ok(Ok(CaptureResult::Png(img))) => {
                         trace!("Screen capture received! (len {})", img.len());
                         save(
                             &target,
@@ -202,12 +597,32 @@ pub fn web_worker(
                         )
                         .unwrap();
                     }
+                    Ok(Ok(CaptureResult::Pdf(path))) => {
+                        // webkit already wrote the PDF to `path`; report
+                        // it directly instead of routing it through
+                        // `save`'s pixbuf/PNG write path.
+                        trace!("PDF capture received at {}", path.display());
+                        report_tx_clone
+                            .send(ReportMessage::Screenshot {
+                                target: target.clone(),
+                                path,
+                            })
+                            .unwrap();
+                    }
                     Ok(Err(e)) => {
                         warn!("Capture failed: {}", e);
                     }
                     Err(e) => {
-                        warn!("Channel disconnected: {}", e);
-                        break;
+                        warn!("Abandoning target `{}`: {}", target, e);
+                        report_tx_clone
+                            .send(ReportMessage::Failure {
+                                target: target.clone(),
+                                error: e.clone(),
+                            })
+                            .unwrap();
+                        if caught_ctrl_c_clone.load(Ordering::SeqCst) {
+                            break;
+                        }
                     }
                 }
             }
@@ -215,8 +630,9 @@ pub fn web_worker(
             // Reached end of input list - close the window
             trace!("Reached end of input list, sending window close request");
             targets_exhausted_clone.store(true, Ordering::SeqCst);
-            sender.send(GuiMessage::Exit).unwrap();
-            //end_of_targets_tx.send(()).unwrap();
+            if sender.send_blocking(GuiMessage::Exit).is_err() {
+                warn!("GUI channel already closed, window may still be open");
+            }
         });
     });
 
@@ -0,0 +1,81 @@
+/*
+ *   This file is part of NCC Group Scrying https://github.com/nccgroup/scrying
+ *   Copyright 2020 David Young <david(dot)young(at)nccgroup(dot)com>
+ *   Released as open source by NCC Group Plc - https://www.nccgroup.com
+ *
+ *   Scrying is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Scrying is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Scrying.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+mod linux;
+mod webdriver;
+
+use crate::{
+    argparse::{Opts, WebBackend},
+    parsing::Target,
+    reporting::ReportMessage,
+    InputLists,
+};
+use std::sync::{atomic::AtomicBool, mpsc, Arc};
+
+// Size of the GTK window/viewport used for non-full-page web captures.
+pub(crate) const WIDTH: i32 = 1920;
+pub(crate) const HEIGHT: i32 = 1080;
+
+// Entry point called by the rest of the program for all web targets.
+// Dispatches to whichever capture backend `--web-backend` selected; both
+// backends report through the same `save`/`ReportMessage` pipeline below
+// so the rest of the program doesn't need to care which one ran.
+pub fn web_worker(
+    targets: Arc<InputLists>,
+    opts: Arc<Opts>,
+    report_tx: mpsc::Sender<ReportMessage>,
+    caught_ctrl_c: Arc<AtomicBool>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match opts.web_backend {
+        WebBackend::Webkit => {
+            linux::web_worker(targets, opts, report_tx, caught_ctrl_c)
+        }
+        WebBackend::Webdriver => webdriver::webdriver_worker(
+            targets,
+            opts,
+            report_tx,
+            caught_ctrl_c,
+        ),
+    }
+}
+
+// Write a capture to disk under `output_dir`, named after the target it
+// came from, and report the resulting path. Shared by every backend so
+// output naming and reporting stay consistent regardless of which one
+// produced the bytes.
+pub(crate) fn save(
+    target: &Target,
+    output_dir: &std::path::Path,
+    img: &[u8],
+    report_tx: &mpsc::Sender<ReportMessage>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(output_dir)?;
+    let stem: String = target
+        .to_string()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    let path = output_dir.join(format!("{}.png", stem));
+    std::fs::write(&path, img)?;
+    report_tx.send(ReportMessage::Screenshot {
+        target: target.clone(),
+        path,
+    })?;
+    Ok(())
+}
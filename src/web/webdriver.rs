@@ -0,0 +1,145 @@
+/*
+ *   This file is part of NCC Group Scrying https://github.com/nccgroup/scrying
+ *   Copyright 2020 David Young <david(dot)young(at)nccgroup(dot)com>
+ *   Released as open source by NCC Group Plc - https://www.nccgroup.com
+ *
+ *   Scrying is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Scrying is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Scrying.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Alternative to `linux`'s webkit2gtk worker: drives a real browser over
+// the W3C WebDriver protocol (chromedriver/geckodriver) instead of
+// embedding webkit2gtk. Selected with `--web-backend webdriver`, pointed
+// at a running driver endpoint with `--webdriver-url`. Feeds the same
+// `save`/`ReportMessage` pipeline as `web_worker` so reporting is
+// unchanged regardless of which backend captured the image.
+
+use super::save;
+use crate::{
+    argparse::Opts, parsing::Target, reporting::ReportMessage, InputLists,
+};
+#[allow(unused)]
+use log::{debug, error, info, trace, warn};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc, Arc,
+};
+use std::time::{Duration, Instant};
+use webdriver_client::{http::HttpDriverBuilder, Driver, DriverSession};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// Block until `document.readyState` reports "complete" or the timeout
+// elapses, mirroring the `LoadEvent::Finished` wait in `web_worker`.
+// Also bails early if `caught_ctrl_c` is set while we're polling, so a
+// slow-loading page can't hold up Ctrl+C, just as the webkit worker's
+// own `wait_for_capture` does.
+fn wait_for_document_ready(
+    session: &DriverSession,
+    timeout: Duration,
+    caught_ctrl_c: &AtomicBool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if caught_ctrl_c.load(Ordering::SeqCst) {
+            return Err("Interrupted by Ctrl+C".into());
+        }
+        let ready_state =
+            session.execute("return document.readyState", vec![])?;
+        if ready_state.as_str() == Some("complete") {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(
+                "Timed out waiting for document to finish loading".into()
+            );
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+pub fn webdriver_worker(
+    targets: Arc<InputLists>,
+    opts: Arc<Opts>,
+    report_tx: mpsc::Sender<ReportMessage>,
+    caught_ctrl_c: Arc<AtomicBool>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let driver = HttpDriverBuilder::default()
+        .url(&opts.webdriver_url)
+        .build()?;
+    let session = driver.session(&Default::default())?;
+
+    for target in &targets.web_targets {
+        if caught_ctrl_c.load(Ordering::SeqCst) {
+            break;
+        }
+
+        let uri = match target {
+            Target::Url(u) => u.as_str().to_string(),
+            Target::File(path) => {
+                if !opts.allow_file {
+                    warn!(
+                        "Refusing to load local file target `{}` \
+                         without --allow-file",
+                        target
+                    );
+                    continue;
+                }
+                // No sanitization of the path is performed here;
+                // --allow-file is an explicit opt-in to the
+                // local-file-read and SSRF-style risks of loading
+                // file:// content.
+                format!("file://{}", path.display())
+            }
+            _ => {
+                warn!("Target `{}` is not a URL!", target);
+                continue;
+            }
+        };
+
+        if let Err(e) = session.go(uri.as_str()) {
+            warn!("Failed to navigate to `{}`: {}", uri, e);
+            continue;
+        }
+
+        if let Err(e) = wait_for_document_ready(
+            &session,
+            Duration::from_secs(opts.web_timeout),
+            &caught_ctrl_c,
+        ) {
+            warn!("Capture failed for `{}`: {}", uri, e);
+            if caught_ctrl_c.load(Ordering::SeqCst) {
+                break;
+            }
+            continue;
+        }
+
+        match session.screenshot() {
+            Ok(b64_png) => match base64::decode(&b64_png) {
+                Ok(png) => {
+                    trace!("Screen capture received! (len {})", png.len());
+                    save(&target, &opts.output_dir, &png, &report_tx)?;
+                }
+                Err(e) => {
+                    warn!("Failed to decode screenshot for `{}`: {}", uri, e);
+                }
+            },
+            Err(e) => {
+                warn!("Capture failed for `{}`: {}", uri, e);
+            }
+        }
+    }
+
+    session.close()?;
+    Ok(())
+}